@@ -1,20 +1,44 @@
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, Command, Stdio};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::thread;
 use tauri::{AppHandle, Emitter};
+use tokio::sync::oneshot;
 use uuid::Uuid;
 
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
+use crate::capabilities::{self, CapabilitiesManager};
+use crate::config::{AgentConfig, TransportConfig};
+use crate::protocol::{self, IncomingMessage, JsonRpcCall, JsonRpcError, JsonRpcReply, RequestId};
+use crate::session::{SessionRecord, SessionStore};
+use crate::transport::{SocketTransport, StdioTransport, Transport};
 
-#[cfg(not(target_os = "windows"))]
-use shell_escape;
+/// How long `call` waits for a response before giving up on an agent that
+/// never replies.
+const CALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
-use crate::config::AgentConfig;
+/// How many inbound JSON-RPC lines we keep per agent so a reloaded window
+/// can rehydrate its conversation via `reattach_agent`.
+const SCROLLBACK_CAPACITY: usize = 200;
+
+/// Label of an optional dashboard window that wants every agent's traffic in
+/// addition to whatever window owns each agent.
+const MONITOR_WINDOW_LABEL: &str = "monitor";
+
+/// Emits an event to the window that owns the agent, plus the monitor window
+/// (if one is open) so a "watch everything" view keeps working.
+fn emit_scoped<S: Serialize + Clone>(
+    app_handle: &AppHandle,
+    window_label: &str,
+    event: &str,
+    payload: S,
+) {
+    let _ = app_handle.emit_to(window_label, event, payload.clone());
+    if window_label != MONITOR_WINDOW_LABEL {
+        let _ = app_handle.emit_to(MONITOR_WINDOW_LABEL, event, payload);
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentInstance {
@@ -34,20 +58,68 @@ pub struct AgentStderr {
     pub line: String,
 }
 
+/// A server-initiated JSON-RPC notification, forwarded to the frontend as-is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcpNotification {
+    pub agent_id: String,
+    pub method: String,
+    pub params: Value,
+}
+
+/// A server-initiated JSON-RPC request. The frontend answers it by id via
+/// `respond_to_agent`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcpRequest {
+    pub agent_id: String,
+    pub id: RequestId,
+    pub method: String,
+    pub params: Value,
+}
+
+/// A `session/request_permission` call that no capability rule could decide,
+/// forwarded to the frontend to ask the user. Answered by id via
+/// `respond_permission`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcpPermissionPrompt {
+    pub agent_id: String,
+    pub id: RequestId,
+    pub method: String,
+    pub params: Value,
+}
+
+type PendingCalls = Arc<RwLock<HashMap<RequestId, oneshot::Sender<Result<Value, JsonRpcError>>>>>;
+
+/// `session/request_permission` requests awaiting a decision from the
+/// frontend, keyed by request id, holding the original request params so we
+/// can map the eventual decision onto one of the `options` the agent offered.
+type PendingPermissions = Arc<RwLock<HashMap<RequestId, Value>>>;
+
 struct RunningAgent {
-    #[allow(dead_code)]
-    child: Child,
-    stdin: Arc<RwLock<std::process::ChildStdin>>,
+    transport: Arc<dyn Transport>,
+    pending_calls: PendingCalls,
+    pending_permissions: PendingPermissions,
+    next_id: Arc<AtomicU64>,
+    scrollback: Arc<RwLock<VecDeque<String>>>,
+    /// Label of the window this agent was spawned from, used to scope its
+    /// events and to clean it up when that window closes.
+    window_label: String,
 }
 
 pub struct AgentManager {
     agents: Arc<RwLock<HashMap<String, RunningAgent>>>,
+    session_store: Arc<SessionStore>,
+    capabilities: Arc<RwLock<Option<CapabilitiesManager>>>,
 }
 
 impl AgentManager {
-    pub fn new() -> Self {
+    pub fn new(
+        session_store: Arc<SessionStore>,
+        capabilities: Arc<RwLock<Option<CapabilitiesManager>>>,
+    ) -> Self {
         Self {
             agents: Arc::new(RwLock::new(HashMap::new())),
+            session_store,
+            capabilities,
         }
     }
 
@@ -55,116 +127,192 @@ impl AgentManager {
         &self,
         name: String,
         config: &AgentConfig,
+        workspace: Option<&str>,
+        window_label: String,
         app_handle: AppHandle,
     ) -> Result<AgentInstance, String> {
         let agent_id = Uuid::new_v4().to_string();
+        let config = config.resolve(workspace, &agent_id);
 
-        // On Windows, we need to use cmd.exe to properly resolve .cmd/.bat files like npx
-        #[cfg(target_os = "windows")]
-        let mut child = {
-            let mut cmd = Command::new("cmd");
-            cmd.arg("/C")
-                .arg(&config.command)
-                .args(&config.args)
-                .envs(&config.env)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .creation_flags(0x08000000); // CREATE_NO_WINDOW
-            cmd.spawn()
-                .map_err(|e| format!("Failed to spawn agent: {}", e))?
-        };
-
-        // On macOS/Unix, we need to use /bin/sh -c to properly resolve commands in PATH
-        #[cfg(not(target_os = "windows"))]
-        let mut child = {
-            use std::borrow::Cow;
-
-            // Build shell command with proper quoting for command and arguments
-            let escaped_command = shell_escape::escape(Cow::Borrowed(config.command.as_str()));
-            let shell_command = if config.args.is_empty() {
-                escaped_command.to_string()
-            } else {
-                let quoted_args: Vec<String> = config
-                    .args
-                    .iter()
-                    .map(|arg| shell_escape::escape(Cow::Borrowed(arg.as_str())).to_string())
-                    .collect();
-                format!("{} {}", escaped_command, quoted_args.join(" "))
-            };
-
-            Command::new("/bin/sh")
-                .arg("-c")
-                .arg(&shell_command)
-                .envs(&config.env)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .map_err(|e| format!("Failed to spawn agent: {}", e))?
+        let transport: Arc<dyn Transport> = match &config.transport {
+            TransportConfig::Stdio { command, args } => Arc::new(StdioTransport::spawn(
+                command,
+                args,
+                &config.env,
+                config.cwd.as_deref(),
+            )?),
+            TransportConfig::Remote { url } => Arc::new(SocketTransport::connect(url)?),
         };
 
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| "Failed to get stdin".to_string())?;
-
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| "Failed to get stdout".to_string())?;
-
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| "Failed to get stderr".to_string())?;
+        let pid = transport.pid();
+        self.session_store.record(SessionRecord {
+            agent_id: agent_id.clone(),
+            name: name.clone(),
+            pid,
+            started_at: pid.and_then(crate::session::SessionStore::process_start_time),
+        })?;
 
-        let stdin = Arc::new(RwLock::new(stdin));
+        let pending_calls: PendingCalls = Arc::new(RwLock::new(HashMap::new()));
+        let pending_permissions: PendingPermissions = Arc::new(RwLock::new(HashMap::new()));
+        let next_id = Arc::new(AtomicU64::new(1));
+        let scrollback: Arc<RwLock<VecDeque<String>>> =
+            Arc::new(RwLock::new(VecDeque::with_capacity(SCROLLBACK_CAPACITY)));
 
-        // Spawn a thread to read stdout and emit events
+        // Feed inbound lines through the JSON-RPC dispatcher and emit events
         let agent_id_clone = agent_id.clone();
+        let agent_name_clone = name.clone();
+        let window_label_clone = window_label.clone();
         let app_handle_clone = app_handle.clone();
         let agents_clone = Arc::clone(&self.agents);
+        let pending_calls_clone = Arc::clone(&pending_calls);
+        let pending_permissions_clone = Arc::clone(&pending_permissions);
+        let scrollback_clone = Arc::clone(&scrollback);
+        let capabilities_clone = Arc::clone(&self.capabilities);
+        let transport_clone = Arc::clone(&transport);
+
+        let on_line: Box<dyn Fn(String) + Send + Sync> = Box::new(move |message| {
+            {
+                let mut scrollback = scrollback_clone.write();
+                if scrollback.len() == SCROLLBACK_CAPACITY {
+                    scrollback.pop_front();
+                }
+                scrollback.push_back(message.clone());
+            }
 
-        thread::spawn(move || {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                match line {
-                    Ok(message) => {
-                        let agent_message = AgentMessage {
-                            agent_id: agent_id_clone.clone(),
-                            message,
-                        };
-                        let _ = app_handle_clone.emit("agent-message", agent_message);
+            match protocol::parse_line(&message) {
+                Ok(IncomingMessage::Response { id, result, error }) => {
+                    if let Some(tx) = pending_calls_clone.write().remove(&id) {
+                        let _ = tx.send(match error {
+                            Some(error) => Err(error),
+                            None => Ok(result.unwrap_or(Value::Null)),
+                        });
                     }
-                    Err(_) => break,
+                }
+                Ok(IncomingMessage::Request { id, method, params })
+                    if method == capabilities::PERMISSION_REQUEST_METHOD =>
+                {
+                    let (kind, path) = capabilities::extract_tool_call(&params);
+                    let decision = capabilities_clone
+                        .read()
+                        .as_ref()
+                        .and_then(|cm| cm.evaluate(&agent_name_clone, &kind, path.as_deref()));
+
+                    match decision {
+                        Some(decision) => {
+                            let reply = JsonRpcReply {
+                                jsonrpc: "2.0",
+                                id,
+                                result: Some(decision.to_result(&params)),
+                                error: None,
+                            };
+                            if let Ok(line) = serde_json::to_string(&reply) {
+                                let _ = transport_clone.send(&line);
+                            }
+                        }
+                        None => {
+                            pending_permissions_clone
+                                .write()
+                                .insert(id.clone(), params.clone());
+                            let prompt = AcpPermissionPrompt {
+                                agent_id: agent_id_clone.clone(),
+                                id,
+                                method,
+                                params,
+                            };
+                            emit_scoped(
+                                &app_handle_clone,
+                                &window_label_clone,
+                                "acp-permission-prompt",
+                                prompt,
+                            );
+                        }
+                    }
+                }
+                Ok(IncomingMessage::Request { id, method, params }) => {
+                    let request = AcpRequest {
+                        agent_id: agent_id_clone.clone(),
+                        id,
+                        method,
+                        params,
+                    };
+                    emit_scoped(
+                        &app_handle_clone,
+                        &window_label_clone,
+                        "acp-request",
+                        request,
+                    );
+                }
+                Ok(IncomingMessage::Notification { method, params }) => {
+                    let notification = AcpNotification {
+                        agent_id: agent_id_clone.clone(),
+                        method,
+                        params,
+                    };
+                    emit_scoped(
+                        &app_handle_clone,
+                        &window_label_clone,
+                        "acp-notification",
+                        notification,
+                    );
+                }
+                Err(_) => {
+                    // Not JSON-RPC (e.g. stray log output) - surface it as before
+                    let agent_message = AgentMessage {
+                        agent_id: agent_id_clone.clone(),
+                        message,
+                    };
+                    emit_scoped(
+                        &app_handle_clone,
+                        &window_label_clone,
+                        "agent-message",
+                        agent_message,
+                    );
                 }
             }
-            // Agent process ended, remove from map
-            agents_clone.write().remove(&agent_id_clone);
-            let _ = app_handle_clone.emit("agent-closed", agent_id_clone);
         });
 
-        // Spawn a thread to read stderr and emit events (for startup progress)
         let agent_id_clone2 = agent_id.clone();
+        let window_label_clone2 = window_label.clone();
         let app_handle_clone2 = app_handle.clone();
-        thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                match line {
-                    Ok(line_content) => {
-                        let stderr_msg = AgentStderr {
-                            agent_id: agent_id_clone2.clone(),
-                            line: line_content,
-                        };
-                        let _ = app_handle_clone2.emit("agent-stderr", stderr_msg);
-                    }
-                    Err(_) => break,
-                }
-            }
+        let on_stderr: Box<dyn Fn(String) + Send + Sync> = Box::new(move |line_content| {
+            let stderr_msg = AgentStderr {
+                agent_id: agent_id_clone2.clone(),
+                line: line_content,
+            };
+            emit_scoped(
+                &app_handle_clone2,
+                &window_label_clone2,
+                "agent-stderr",
+                stderr_msg,
+            );
         });
 
-        let running_agent = RunningAgent { child, stdin };
+        let agent_id_clone3 = agent_id.clone();
+        let window_label_clone3 = window_label.clone();
+        let app_handle_clone3 = app_handle.clone();
+        let session_store_clone = Arc::clone(&self.session_store);
+        let on_closed: Box<dyn Fn() + Send + Sync> = Box::new(move || {
+            // Agent process ended, remove from map
+            agents_clone.write().remove(&agent_id_clone3);
+            let _ = session_store_clone.remove(&agent_id_clone3);
+            emit_scoped(
+                &app_handle_clone3,
+                &window_label_clone3,
+                "agent-closed",
+                agent_id_clone3.clone(),
+            );
+        });
+
+        transport.spawn_reader(on_line, on_stderr, on_closed);
+
+        let running_agent = RunningAgent {
+            transport,
+            pending_calls,
+            pending_permissions,
+            next_id,
+            scrollback,
+            window_label,
+        };
         self.agents.write().insert(agent_id.clone(), running_agent);
 
         Ok(AgentInstance { id: agent_id, name })
@@ -176,33 +324,162 @@ impl AgentManager {
             .get(agent_id)
             .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
 
-        let mut stdin = agent.stdin.write();
-        writeln!(stdin, "{}", message).map_err(|e| format!("Failed to write to stdin: {}", e))?;
-        stdin
-            .flush()
-            .map_err(|e| format!("Failed to flush stdin: {}", e))?;
+        agent.transport.send(message)
+    }
+
+    /// Issues a JSON-RPC call to the agent and waits for the matching
+    /// response, resolving (or timing out) via the pending-calls table
+    /// maintained by the transport's reader thread.
+    pub async fn call(&self, agent_id: &str, method: &str, params: Value) -> Result<Value, String> {
+        let (id, pending_calls) = {
+            let agents = self.agents.read();
+            let agent = agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            let id = RequestId::from(agent.next_id.fetch_add(1, Ordering::SeqCst));
+            (id, Arc::clone(&agent.pending_calls))
+        };
+
+        let (tx, rx) = oneshot::channel();
+        pending_calls.write().insert(id.clone(), tx);
+
+        let call = JsonRpcCall {
+            jsonrpc: "2.0",
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+        };
+        let line = serde_json::to_string(&call).map_err(|e| e.to_string())?;
+
+        {
+            let agents = self.agents.read();
+            let agent = agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            agent.transport.send(&line)?;
+        }
+
+        match tokio::time::timeout(CALL_TIMEOUT, rx).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(error))) => Err(format!("{} (code {})", error.message, error.code)),
+            Ok(Err(_)) => {
+                pending_calls.write().remove(&id);
+                Err(format!("Agent '{}' closed before responding", agent_id))
+            }
+            Err(_) => {
+                pending_calls.write().remove(&id);
+                Err(format!("Timed out waiting for '{}' to respond", method))
+            }
+        }
+    }
+
+    /// Answers a server-initiated request (delivered to the frontend via the
+    /// `acp-request` event) by writing the matching JSON-RPC response.
+    pub fn respond(
+        &self,
+        agent_id: &str,
+        id: RequestId,
+        result: Option<Value>,
+        error: Option<JsonRpcError>,
+    ) -> Result<(), String> {
+        let agents = self.agents.read();
+        let agent = agents
+            .get(agent_id)
+            .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+
+        let reply = JsonRpcReply {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        };
+        let line = serde_json::to_string(&reply).map_err(|e| e.to_string())?;
+        agent.transport.send(&line)
+    }
+
+    /// Answers a `session/request_permission` prompt (delivered to the
+    /// frontend via the `acp-permission-prompt` event) by mapping `decision`
+    /// onto one of the `options` from the original request and writing the
+    /// matching JSON-RPC response.
+    pub fn respond_permission(
+        &self,
+        agent_id: &str,
+        id: RequestId,
+        decision: capabilities::PermissionDecision,
+    ) -> Result<(), String> {
+        let request_params = {
+            let agents = self.agents.read();
+            let agent = agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            agent
+                .pending_permissions
+                .write()
+                .remove(&id)
+                .unwrap_or(Value::Null)
+        };
 
-        Ok(())
+        self.respond(agent_id, id, Some(decision.to_result(&request_params)), None)
     }
 
     pub fn kill_agent(&self, agent_id: &str) -> Result<(), String> {
         let mut agents = self.agents.write();
-        if let Some(mut agent) = agents.remove(agent_id) {
-            agent
-                .child
-                .kill()
-                .map_err(|e| format!("Failed to kill agent: {}", e))?;
+        if let Some(agent) = agents.remove(agent_id) {
+            agent.transport.close()?;
+        }
+        self.session_store.remove(agent_id)
+    }
+
+    /// Kills every agent owned by `window_label`, so closing a chat window
+    /// doesn't leave its agents running with nowhere to deliver events.
+    pub fn kill_agents_for_window(&self, window_label: &str) {
+        let owned: Vec<String> = self
+            .agents
+            .read()
+            .iter()
+            .filter(|(_, agent)| agent.window_label == window_label)
+            .map(|(agent_id, _)| agent_id.clone())
+            .collect();
+
+        for agent_id in owned {
+            let _ = self.kill_agent(&agent_id);
         }
-        Ok(())
     }
 
     pub fn list_running_agents(&self) -> Vec<String> {
         self.agents.read().keys().cloned().collect()
     }
-}
 
-impl Default for AgentManager {
-    fn default() -> Self {
-        Self::new()
+    /// Returns the buffered JSON-RPC lines for an agent so a reloaded window
+    /// can replay them and rehydrate its conversation.
+    pub fn scrollback(&self, agent_id: &str) -> Result<Vec<String>, String> {
+        let agents = self.agents.read();
+        let agent = agents
+            .get(agent_id)
+            .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        Ok(agent.scrollback.read().iter().cloned().collect())
+    }
+
+    /// Sessions left running by a previous instance of the app that this
+    /// instance hasn't adopted into its in-memory agent map. Their stdio
+    /// pipes don't survive a restart, so they can be discovered and killed
+    /// but not driven.
+    pub fn discover_orphaned_sessions(&self) -> Vec<SessionRecord> {
+        let tracked = self.agents.read();
+        self.session_store
+            .discover_live()
+            .into_iter()
+            .filter(|session| !tracked.contains_key(&session.agent_id))
+            .collect()
+    }
+
+    /// Kills a session discovered via `discover_orphaned_sessions` by PID,
+    /// since its transport (and any in-memory record of it) didn't survive
+    /// the restart that orphaned it in the first place.
+    pub fn kill_orphaned_session(&self, agent_id: &str) -> Result<(), String> {
+        if self.agents.read().contains_key(agent_id) {
+            return self.kill_agent(agent_id);
+        }
+        self.session_store.kill(agent_id)
     }
 }
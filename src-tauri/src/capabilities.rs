@@ -0,0 +1,249 @@
+use indexmap::IndexMap;
+use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+
+/// The JSON-RPC method an ACP agent calls to ask permission before running
+/// a tool or editing a file.
+pub const PERMISSION_REQUEST_METHOD: &str = "session/request_permission";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionDecision {
+    Allow,
+    Deny,
+}
+
+/// One of the options a `session/request_permission` call offers the user to
+/// choose from, e.g. `{"optionId": "allow-1", "name": "Allow", "kind":
+/// "allow_once"}`.
+#[derive(Debug, Clone, Deserialize)]
+struct PermissionOption {
+    #[serde(rename = "optionId")]
+    option_id: String,
+    #[serde(default)]
+    kind: String,
+}
+
+impl PermissionDecision {
+    /// The JSON-RPC result sent back to the agent for this decision: an ACP
+    /// `RequestPermissionOutcome` selecting whichever of the request's
+    /// `options` matches (an `allow_*` kind for `Allow`, a `reject_*` kind
+    /// for `Deny`), or `cancelled` if the request offered nothing we can map
+    /// the decision onto.
+    pub fn to_result(self, request_params: &Value) -> Value {
+        let wanted_prefix = match self {
+            PermissionDecision::Allow => "allow",
+            PermissionDecision::Deny => "reject",
+        };
+
+        let options: Vec<PermissionOption> = request_params
+            .get("options")
+            .cloned()
+            .and_then(|options| serde_json::from_value(options).ok())
+            .unwrap_or_default();
+
+        let option_id = options
+            .into_iter()
+            .find(|option| option.kind.starts_with(wanted_prefix))
+            .map(|option| option.option_id);
+
+        match option_id {
+            Some(option_id) => {
+                serde_json::json!({ "outcome": { "outcome": "selected", "optionId": option_id } })
+            }
+            None => serde_json::json!({ "outcome": { "outcome": "cancelled" } }),
+        }
+    }
+}
+
+/// One rule in an agent's capability policy. Rules are tried in order; the
+/// first one whose `kind` and (if present) `allowed_paths` match wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRule {
+    /// The tool call kind this rule matches (e.g. `read`, `edit`,
+    /// `execute`), or `"*"` to match any kind.
+    pub kind: String,
+    pub decision: PermissionDecision,
+    /// Restricts the rule to paths under one of these roots. Empty means
+    /// "any path" - appropriate for rules that aren't about a path at all,
+    /// like denying shell execution outright.
+    #[serde(default)]
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentCapabilities {
+    #[serde(default)]
+    pub rules: Vec<PermissionRule>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapabilitiesConfig {
+    #[serde(default)]
+    pub agents: IndexMap<String, AgentCapabilities>,
+}
+
+pub struct CapabilitiesManager {
+    config: Arc<RwLock<CapabilitiesConfig>>,
+    config_path: PathBuf,
+    #[allow(dead_code)]
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl CapabilitiesManager {
+    pub fn new(app: &AppHandle, config_dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+        let config_path = config_dir.join("capabilities.json");
+
+        let config = if config_path.exists() {
+            load_capabilities(&config_path)?
+        } else {
+            let default_config = CapabilitiesConfig::default();
+            save_capabilities(&config_path, &default_config)?;
+            default_config
+        };
+
+        let config = Arc::new(RwLock::new(config));
+        let config_clone = Arc::clone(&config);
+        let config_path_clone = config_path.clone();
+        let app_handle = app.clone();
+
+        let watcher = setup_watcher(config_clone, config_path_clone, app_handle)?;
+
+        Ok(Self {
+            config,
+            config_path,
+            watcher: Some(watcher),
+        })
+    }
+
+    pub fn get_config(&self) -> CapabilitiesConfig {
+        self.config.read().clone()
+    }
+
+    pub fn get_config_path(&self) -> PathBuf {
+        self.config_path.clone()
+    }
+
+    /// Evaluates a tool-execution permission request against the policy for
+    /// `agent_name`. Returns `None` if no rule matches, meaning the user
+    /// must be asked.
+    pub fn evaluate(
+        &self,
+        agent_name: &str,
+        kind: &str,
+        path: Option<&Path>,
+    ) -> Option<PermissionDecision> {
+        let config = self.config.read();
+        let rules = config
+            .agents
+            .get(agent_name)
+            .map(|capabilities| capabilities.rules.as_slice())
+            .unwrap_or(&[]);
+
+        for rule in rules {
+            if rule.kind != "*" && rule.kind != kind {
+                continue;
+            }
+            if !rule.allowed_paths.is_empty() {
+                match path {
+                    Some(path) if is_within_allowlist(path, &rule.allowed_paths) => {}
+                    _ => continue,
+                }
+            }
+            return Some(rule.decision);
+        }
+
+        None
+    }
+}
+
+/// Resolves `path` and checks it falls under one of `roots` once both are
+/// canonicalized, so a `../` traversal can't escape an allowed directory.
+fn is_within_allowlist(path: &Path, roots: &[PathBuf]) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    roots.iter().any(|root| {
+        root.canonicalize()
+            .map(|root| path.starts_with(root))
+            .unwrap_or(false)
+    })
+}
+
+/// Pulls the tool call `kind` and, if present, the filesystem path it
+/// targets out of a `session/request_permission` request's params.
+pub fn extract_tool_call(params: &Value) -> (String, Option<PathBuf>) {
+    let tool_call = params.get("toolCall");
+
+    let kind = tool_call
+        .and_then(|tool_call| tool_call.get("kind"))
+        .and_then(Value::as_str)
+        .unwrap_or("other")
+        .to_string();
+
+    let path = tool_call
+        .and_then(|tool_call| tool_call.get("path"))
+        .or_else(|| {
+            tool_call
+                .and_then(|tool_call| tool_call.get("locations"))
+                .and_then(|locations| locations.get(0))
+                .and_then(|location| location.get("path"))
+        })
+        .and_then(Value::as_str)
+        .map(PathBuf::from);
+
+    (kind, path)
+}
+
+fn load_capabilities(path: &PathBuf) -> Result<CapabilitiesConfig, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+fn save_capabilities(path: &PathBuf, config: &CapabilitiesConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+fn setup_watcher(
+    config: Arc<RwLock<CapabilitiesConfig>>,
+    config_path: PathBuf,
+    app_handle: AppHandle,
+) -> Result<RecommendedWatcher, String> {
+    let config_path_for_watcher = config_path.clone();
+
+    let mut watcher = RecommendedWatcher::new(
+        move |res: Result<notify::Event, notify::Error>| {
+            if let Ok(event) = res {
+                match event.kind {
+                    EventKind::Modify(_) | EventKind::Create(_) => {
+                        if event.paths.iter().any(|p| p == &config_path_for_watcher) {
+                            if let Ok(new_config) = load_capabilities(&config_path_for_watcher) {
+                                *config.write() = new_config.clone();
+                                let _ = app_handle.emit("capabilities-changed", new_config);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+        Config::default(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if let Some(parent) = config_path.parent() {
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(watcher)
+}
@@ -7,10 +7,205 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 
+/// How the UI talks to an agent's process: a locally spawned child over
+/// stdio, or a remote endpoint reachable over a socket.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportConfig {
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// `url` is `tcp://host:port` for a raw socket, or `ws://`/`wss://` for
+    /// a WebSocket endpoint.
+    Remote {
+        url: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct AgentConfig {
-    pub command: String,
-    pub args: Vec<String>,
+    #[serde(flatten)]
+    pub transport: TransportConfig,
+    /// Working directory the agent process is spawned in. Supports the same
+    /// `${workspace}`/`${agent_id}`/`${env:VAR}` placeholders as `args`/`env`.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// Extra environment variables merged over the inherited environment.
+    /// Values may reference `${workspace}`, `${agent_id}`, or `${env:VAR}`.
+    #[serde(default)]
+    pub env: IndexMap<String, String>,
+}
+
+/// Deserialized by hand rather than derived so that `agents.json` files
+/// written before the `type`-tagged `TransportConfig` existed - which just
+/// had `command`/`args` at the top level - keep loading as `Stdio` agents
+/// instead of failing config load on upgrade.
+impl<'de> Deserialize<'de> for AgentConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawAgentConfig {
+            #[serde(rename = "type")]
+            kind: Option<String>,
+            command: Option<String>,
+            #[serde(default)]
+            args: Vec<String>,
+            url: Option<String>,
+            #[serde(default)]
+            cwd: Option<PathBuf>,
+            #[serde(default)]
+            env: IndexMap<String, String>,
+        }
+
+        let raw = RawAgentConfig::deserialize(deserializer)?;
+        let transport = match raw.kind.as_deref() {
+            Some("stdio") | None => TransportConfig::Stdio {
+                command: raw
+                    .command
+                    .ok_or_else(|| serde::de::Error::missing_field("command"))?,
+                args: raw.args,
+            },
+            Some("remote") => TransportConfig::Remote {
+                url: raw
+                    .url
+                    .ok_or_else(|| serde::de::Error::missing_field("url"))?,
+            },
+            Some(other) => {
+                return Err(serde::de::Error::unknown_variant(
+                    other,
+                    &["stdio", "remote"],
+                ))
+            }
+        };
+
+        Ok(AgentConfig {
+            transport,
+            cwd: raw.cwd,
+            env: raw.env,
+        })
+    }
+}
+
+impl AgentConfig {
+    /// Expands `${workspace}`, `${agent_id}`, and `${env:VAR}` placeholders in
+    /// this config's args, env values, and cwd, producing a config ready to
+    /// hand to a `Transport`. `workspace` lets one config entry be reused
+    /// across projects by pointing it at a different directory per spawn.
+    pub fn resolve(&self, workspace: Option<&str>, agent_id: &str) -> AgentConfig {
+        let transport = match &self.transport {
+            TransportConfig::Stdio { command, args } => TransportConfig::Stdio {
+                command: expand_template(command, workspace, agent_id),
+                args: args
+                    .iter()
+                    .map(|arg| expand_template(arg, workspace, agent_id))
+                    .collect(),
+            },
+            TransportConfig::Remote { url } => TransportConfig::Remote {
+                url: expand_template(url, workspace, agent_id),
+            },
+        };
+
+        let cwd = self
+            .cwd
+            .as_ref()
+            .map(|cwd| PathBuf::from(expand_template(&cwd.to_string_lossy(), workspace, agent_id)));
+
+        let env = self
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), expand_template(value, workspace, agent_id)))
+            .collect();
+
+        AgentConfig {
+            transport,
+            cwd,
+            env,
+        }
+    }
+}
+
+/// Replaces `${workspace}`, `${agent_id}`, and `${env:VAR}` placeholders in
+/// `value`. Unknown or unset placeholders expand to an empty string rather
+/// than failing the spawn outright.
+fn expand_template(value: &str, workspace: Option<&str>, agent_id: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let Some(end) = after_open.find('}') else {
+            result.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+
+        let placeholder = &after_open[..end];
+        let expanded = match placeholder {
+            "workspace" => workspace.unwrap_or_default().to_string(),
+            "agent_id" => agent_id.to_string(),
+            _ => placeholder
+                .strip_prefix("env:")
+                .and_then(|var| std::env::var(var).ok())
+                .unwrap_or_default(),
+        };
+        result.push_str(&expanded);
+        rest = &after_open[end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Checks that every `${env:VAR}` placeholder referenced by `config` names a
+/// variable that's actually set, so a typo surfaces when the agent is saved
+/// rather than silently expanding to an empty string at spawn time.
+fn validate_env_refs(config: &AgentConfig) -> Result<(), String> {
+    let mut values: Vec<&str> = config.env.values().map(String::as_str).collect();
+    match &config.transport {
+        TransportConfig::Stdio { command, args } => {
+            values.push(command);
+            values.extend(args.iter().map(String::as_str));
+        }
+        TransportConfig::Remote { url } => values.push(url),
+    }
+    if let Some(cwd) = &config.cwd {
+        if let Some(cwd) = cwd.to_str() {
+            values.push(cwd);
+        }
+    }
+
+    for value in values {
+        for var in referenced_env_vars(value) {
+            if std::env::var(&var).is_err() {
+                return Err(format!(
+                    "Agent config references undefined environment variable '{}'",
+                    var
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts the variable names from every `${env:VAR}` placeholder in `value`.
+fn referenced_env_vars(value: &str) -> Vec<String> {
+    let mut vars = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${env:") {
+        let after_open = &rest[start + "${env:".len()..];
+        let Some(end) = after_open.find('}') else {
+            break;
+        };
+        vars.push(after_open[..end].to_string());
+        rest = &after_open[end + 1..];
+    }
+    vars
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,41 +219,55 @@ impl Default for AgentsConfig {
         agents.insert(
             "GitHub Copilot".to_string(),
             AgentConfig {
-                command: "npx".to_string(),
-                args: vec![
-                    "@github/copilot-language-server@latest".to_string(),
-                    "--acp".to_string(),
-                ],
+                transport: TransportConfig::Stdio {
+                    command: "npx".to_string(),
+                    args: vec![
+                        "@github/copilot-language-server@latest".to_string(),
+                        "--acp".to_string(),
+                    ],
+                },
+                cwd: None,
+                env: IndexMap::new(),
             },
         );
         agents.insert(
             "Claude Code".to_string(),
             AgentConfig {
-                command: "npx".to_string(),
-                args: vec![
-                    "@zed-industries/claude-code-acp@latest".to_string(),
-                ],
+                transport: TransportConfig::Stdio {
+                    command: "npx".to_string(),
+                    args: vec!["@zed-industries/claude-code-acp@latest".to_string()],
+                },
+                cwd: None,
+                env: IndexMap::new(),
             },
         );
         agents.insert(
             "Gemini CLI".to_string(),
             AgentConfig {
-                command: "npx".to_string(),
-                args: vec![
-                    "@google/gemini-cli@latest".to_string(),
-                    "--experimental-acp".to_string(),
-                ],
+                transport: TransportConfig::Stdio {
+                    command: "npx".to_string(),
+                    args: vec![
+                        "@google/gemini-cli@latest".to_string(),
+                        "--experimental-acp".to_string(),
+                    ],
+                },
+                cwd: None,
+                env: IndexMap::new(),
             },
         );
         agents.insert(
             "Qwen Code".to_string(),
             AgentConfig {
-                command: "npx".to_string(),
-                args: vec![
-                    "@qwen-code/qwen-code@latest".to_string(),
-                    "--acp".to_string(),
-                    "--experimental-skills".to_string(),
-                ],
+                transport: TransportConfig::Stdio {
+                    command: "npx".to_string(),
+                    args: vec![
+                        "@qwen-code/qwen-code@latest".to_string(),
+                        "--acp".to_string(),
+                        "--experimental-skills".to_string(),
+                    ],
+                },
+                cwd: None,
+                env: IndexMap::new(),
             },
         );
         AgentsConfig { agents }
@@ -125,6 +334,7 @@ impl ConfigManager {
     }
 
     pub fn add_agent(&self, name: String, config: AgentConfig) -> Result<AgentsConfig, String> {
+        validate_env_refs(&config)?;
         {
             let mut agents_config = self.config.write();
             agents_config.agents.insert(name, config);
@@ -143,6 +353,7 @@ impl ConfigManager {
     }
 
     pub fn update_agent(&self, name: String, config: AgentConfig) -> Result<AgentsConfig, String> {
+        validate_env_refs(&config)?;
         {
             let mut agents_config = self.config.write();
             if agents_config.agents.contains_key(&name) {
@@ -156,19 +367,16 @@ impl ConfigManager {
     }
 }
 
+/// The directory `agents.json`, `sessions.json` and other per-app state live
+/// in.
+pub fn get_config_dir() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|p| p.join("acp-ui"))
+        .ok_or_else(|| "Could not find config directory".to_string())
+}
+
 fn get_config_path() -> Result<PathBuf, String> {
-    #[cfg(target_os = "windows")]
-    {
-        dirs::config_dir()
-            .map(|p| p.join("acp-ui").join("agents.json"))
-            .ok_or_else(|| "Could not find config directory".to_string())
-    }
-    #[cfg(not(target_os = "windows"))]
-    {
-        dirs::config_dir()
-            .map(|p| p.join("acp-ui").join("agents.json"))
-            .ok_or_else(|| "Could not find config directory".to_string())
-    }
+    get_config_dir().map(|p| p.join("agents.json"))
 }
 
 fn load_config(path: &PathBuf) -> Result<AgentsConfig, String> {
@@ -1,14 +1,23 @@
 mod agent;
+mod capabilities;
 mod config;
+mod protocol;
+mod session;
+mod transport;
 
 use agent::{AgentInstance, AgentManager};
-use config::{AgentConfig, AgentsConfig, ConfigManager};
+use capabilities::{CapabilitiesManager, PermissionDecision};
+use config::{AgentConfig, AgentsConfig, ConfigManager, TransportConfig};
 use parking_lot::RwLock;
+use protocol::{JsonRpcError, RequestId};
+use serde_json::Value;
+use session::SessionRecord;
 use std::sync::Arc;
-use tauri::{AppHandle, Manager, State};
+use tauri::{AppHandle, Manager, State, Window};
 
 struct AppState {
     config_manager: Arc<RwLock<Option<ConfigManager>>>,
+    capabilities_manager: Arc<RwLock<Option<CapabilitiesManager>>>,
     agent_manager: AgentManager,
 }
 
@@ -42,6 +51,8 @@ fn get_config_path(state: State<AppState>) -> Result<String, String> {
 #[tauri::command]
 fn spawn_agent(
     name: String,
+    workspace: Option<String>,
+    window: Window,
     state: State<AppState>,
     app_handle: AppHandle,
 ) -> Result<AgentInstance, String> {
@@ -56,9 +67,13 @@ fn spawn_agent(
         .get(&name)
         .ok_or_else(|| format!("Agent '{}' not found in config", name))?;
 
-    state
-        .agent_manager
-        .spawn_agent(name, agent_config, app_handle)
+    state.agent_manager.spawn_agent(
+        name,
+        agent_config,
+        workspace.as_deref(),
+        window.label().to_string(),
+        app_handle,
+    )
 }
 
 #[tauri::command]
@@ -71,23 +86,97 @@ fn kill_agent(agent_id: String, state: State<AppState>) -> Result<(), String> {
     state.agent_manager.kill_agent(&agent_id)
 }
 
+#[tauri::command]
+async fn call_agent(
+    agent_id: String,
+    method: String,
+    params: Value,
+    state: State<'_, AppState>,
+) -> Result<Value, String> {
+    state.agent_manager.call(&agent_id, &method, params).await
+}
+
+#[tauri::command]
+fn respond_to_agent(
+    agent_id: String,
+    id: RequestId,
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    state.agent_manager.respond(&agent_id, id, result, error)
+}
+
+#[tauri::command]
+fn get_capabilities(state: State<AppState>) -> Result<capabilities::CapabilitiesConfig, String> {
+    let capabilities_manager = state.capabilities_manager.read();
+    capabilities_manager
+        .as_ref()
+        .map(|cm| cm.get_config())
+        .ok_or_else(|| "Capabilities manager not initialized".to_string())
+}
+
+#[tauri::command]
+fn get_capabilities_path(state: State<AppState>) -> Result<String, String> {
+    let capabilities_manager = state.capabilities_manager.read();
+    capabilities_manager
+        .as_ref()
+        .map(|cm| cm.get_config_path().to_string_lossy().to_string())
+        .ok_or_else(|| "Capabilities manager not initialized".to_string())
+}
+
+#[tauri::command]
+fn respond_permission(
+    agent_id: String,
+    id: RequestId,
+    decision: PermissionDecision,
+    state: State<AppState>,
+) -> Result<(), String> {
+    state
+        .agent_manager
+        .respond_permission(&agent_id, id, decision)
+}
+
 #[tauri::command]
 fn list_running_agents(state: State<AppState>) -> Vec<String> {
     state.agent_manager.list_running_agents()
 }
 
+#[tauri::command]
+fn reattach_agent(agent_id: String, state: State<AppState>) -> Result<Vec<String>, String> {
+    state.agent_manager.scrollback(&agent_id)
+}
+
+#[tauri::command]
+fn list_orphaned_sessions(state: State<AppState>) -> Vec<SessionRecord> {
+    state.agent_manager.discover_orphaned_sessions()
+}
+
+#[tauri::command]
+fn kill_orphaned_session(agent_id: String, state: State<AppState>) -> Result<(), String> {
+    state.agent_manager.kill_orphaned_session(&agent_id)
+}
+
 #[tauri::command]
 fn add_agent(
     name: String,
-    command: String,
-    args: Vec<String>,
+    transport: TransportConfig,
+    cwd: Option<std::path::PathBuf>,
+    env: indexmap::IndexMap<String, String>,
     state: State<AppState>,
 ) -> Result<AgentsConfig, String> {
     let config_manager = state.config_manager.read();
     config_manager
         .as_ref()
         .ok_or_else(|| "Config manager not initialized".to_string())?
-        .add_agent(name, AgentConfig { command, args })
+        .add_agent(
+            name,
+            AgentConfig {
+                transport,
+                cwd,
+                env,
+            },
+        )
 }
 
 #[tauri::command]
@@ -102,15 +191,23 @@ fn remove_agent(name: String, state: State<AppState>) -> Result<AgentsConfig, St
 #[tauri::command]
 fn update_agent(
     name: String,
-    command: String,
-    args: Vec<String>,
+    transport: TransportConfig,
+    cwd: Option<std::path::PathBuf>,
+    env: indexmap::IndexMap<String, String>,
     state: State<AppState>,
 ) -> Result<AgentsConfig, String> {
     let config_manager = state.config_manager.read();
     config_manager
         .as_ref()
         .ok_or_else(|| "Config manager not initialized".to_string())?
-        .update_agent(name, AgentConfig { command, args })
+        .update_agent(
+            name,
+            AgentConfig {
+                transport,
+                cwd,
+                env,
+            },
+        )
 }
 
 #[tauri::command]
@@ -120,9 +217,17 @@ fn get_machine_id() -> Result<String, String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let config_dir =
+        config::get_config_dir().unwrap_or_else(|_| std::env::temp_dir().join("acp-ui"));
+    let _ = std::fs::create_dir_all(&config_dir);
+    let session_store = Arc::new(session::SessionStore::new(config_dir));
+    let capabilities_manager: Arc<RwLock<Option<CapabilitiesManager>>> =
+        Arc::new(RwLock::new(None));
+
     let app_state = AppState {
         config_manager: Arc::new(RwLock::new(None)),
-        agent_manager: AgentManager::new(),
+        capabilities_manager: Arc::clone(&capabilities_manager),
+        agent_manager: AgentManager::new(session_store, capabilities_manager),
     };
 
     tauri::Builder::default()
@@ -145,8 +250,26 @@ pub fn run() {
                 }
             }
 
+            // Initialize capabilities manager
+            let config_dir =
+                config::get_config_dir().unwrap_or_else(|_| std::env::temp_dir().join("acp-ui"));
+            match CapabilitiesManager::new(&app_handle, config_dir) {
+                Ok(cm) => {
+                    *state.capabilities_manager.write() = Some(cm);
+                }
+                Err(e) => {
+                    eprintln!("Failed to initialize capabilities manager: {}", e);
+                }
+            }
+
             Ok(())
         })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { .. } = event {
+                let state: State<AppState> = window.state();
+                state.agent_manager.kill_agents_for_window(window.label());
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             get_config,
             reload_config,
@@ -154,7 +277,15 @@ pub fn run() {
             spawn_agent,
             send_to_agent,
             kill_agent,
+            call_agent,
+            respond_to_agent,
+            get_capabilities,
+            get_capabilities_path,
+            respond_permission,
             list_running_agents,
+            reattach_agent,
+            list_orphaned_sessions,
+            kill_orphaned_session,
             add_agent,
             remove_agent,
             update_agent,
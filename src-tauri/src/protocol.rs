@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// JSON-RPC 2.0 request ids. The spec permits both integers and strings, and
+/// some ACP agents do send string ids, so we model both rather than assuming
+/// the id is always numeric.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    String(String),
+}
+
+impl From<u64> for RequestId {
+    fn from(id: u64) -> Self {
+        RequestId::Number(id)
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestId::Number(id) => write!(f, "{}", id),
+            RequestId::String(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcCall {
+    pub jsonrpc: &'static str,
+    pub id: RequestId,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcReply {
+    pub jsonrpc: &'static str,
+    pub id: RequestId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+/// A line of agent stdout, classified per the JSON-RPC 2.0 framing rules:
+/// `id` + (`result` or `error`) is a response to one of our calls, `id` +
+/// `method` is a server-initiated request we need to answer, and `method`
+/// alone is a notification.
+#[derive(Debug, Clone)]
+pub enum IncomingMessage {
+    Response {
+        id: RequestId,
+        result: Option<Value>,
+        error: Option<JsonRpcError>,
+    },
+    Request {
+        id: RequestId,
+        method: String,
+        params: Value,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+}
+
+/// Parses a single newline-delimited JSON-RPC message. `BufRead::lines()`
+/// already buffers partial reads until a full line is available, so the only
+/// edge case we need to handle here is a line that isn't valid JSON-RPC at
+/// all (e.g. stray log output mixed into stdout).
+pub fn parse_line(line: &str) -> Result<IncomingMessage, serde_json::Error> {
+    let value: Value = serde_json::from_str(line)?;
+
+    let id = value.get("id").and_then(|id| {
+        if let Some(id) = id.as_u64() {
+            Some(RequestId::Number(id))
+        } else {
+            id.as_str().map(|id| RequestId::String(id.to_string()))
+        }
+    });
+    let method = value
+        .get("method")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let has_result_or_error = value.get("result").is_some() || value.get("error").is_some();
+
+    match (id, method) {
+        (Some(id), None) if has_result_or_error => {
+            let result = value.get("result").cloned();
+            let error = value
+                .get("error")
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()?;
+            Ok(IncomingMessage::Response { id, result, error })
+        }
+        (Some(id), Some(method)) => {
+            let params = value.get("params").cloned().unwrap_or(Value::Null);
+            Ok(IncomingMessage::Request { id, method, params })
+        }
+        (None, Some(method)) => {
+            let params = value.get("params").cloned().unwrap_or(Value::Null);
+            Ok(IncomingMessage::Notification { method, params })
+        }
+        _ => Err(serde::de::Error::custom(
+            "not a well-formed JSON-RPC 2.0 message",
+        )),
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use sysinfo::{Pid, System};
+
+/// Metadata for one spawned agent process, persisted next to `agents.json`
+/// so a restarted app can tell which agents were still running when it last
+/// exited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub agent_id: String,
+    pub name: String,
+    /// `None` for agents reached over a remote transport, which have no
+    /// local process to track.
+    pub pid: Option<u32>,
+    /// The process's start time (seconds since the Unix epoch) at the moment it was
+    /// recorded, used to tell it apart from an unrelated process that the OS
+    /// later recycles `pid` onto. `None` for records written before this
+    /// field existed, which can no longer be verified and are treated as
+    /// dead rather than risking a match against the wrong process.
+    #[serde(default)]
+    pub started_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SessionsFile {
+    sessions: Vec<SessionRecord>,
+}
+
+/// Tracks spawned agent sessions on disk so `discover_live` can find agents
+/// left running by a previous instance of the app instead of silently
+/// orphaning them. Those agents are reported and can be killed, but are not
+/// re-adopted: `list_running_agents` only lists agents spawned by this
+/// instance.
+pub struct SessionStore {
+    path: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(config_dir: PathBuf) -> Self {
+        Self {
+            path: config_dir.join("sessions.json"),
+        }
+    }
+
+    /// Looks up the start time of a just-spawned process so its
+    /// `SessionRecord` can be matched back to the same process later, not
+    /// just the same (possibly since-recycled) PID.
+    pub fn process_start_time(pid: u32) -> Option<u64> {
+        let mut system = System::new();
+        system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+        system.process(Pid::from_u32(pid)).map(|p| p.start_time())
+    }
+
+    pub fn record(&self, session: SessionRecord) -> Result<(), String> {
+        let mut file = self.load();
+        file.sessions.retain(|s| s.agent_id != session.agent_id);
+        file.sessions.push(session);
+        self.save(&file)
+    }
+
+    pub fn remove(&self, agent_id: &str) -> Result<(), String> {
+        let mut file = self.load();
+        file.sessions.retain(|s| s.agent_id != agent_id);
+        self.save(&file)
+    }
+
+    /// Returns the sessions whose process is still alive, pruning any that
+    /// have since died (or whose PID now belongs to a different process)
+    /// from disk. Local stdio pipes don't survive a restart, so these
+    /// processes can't be reattached to for I/O - this only lets the UI show
+    /// they exist and offer to kill them via `kill` instead of leaking them.
+    pub fn discover_live(&self) -> Vec<SessionRecord> {
+        let mut system = System::new();
+        system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+
+        let mut file = self.load();
+        file.sessions.retain(|session| is_same_process(&system, session));
+        let live = file.sessions.clone();
+        let _ = self.save(&file);
+        live
+    }
+
+    /// Kills an orphaned session's process by PID, verifying it's still the
+    /// same process (via `started_at`) before signaling it, then drops the
+    /// record from disk either way since there's nothing more to track.
+    pub fn kill(&self, agent_id: &str) -> Result<(), String> {
+        let mut system = System::new();
+        system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+
+        let file = self.load();
+        if let Some(session) = file.sessions.iter().find(|s| s.agent_id == agent_id) {
+            if is_same_process(&system, session) {
+                // `pid` is guaranteed `Some` here: `is_same_process` only
+                // returns true when both `pid` and `started_at` matched.
+                let pid = Pid::from_u32(session.pid.unwrap());
+                if let Some(process) = system.process(pid) {
+                    if !process.kill() {
+                        return Err(format!("Failed to kill process {}", pid));
+                    }
+                }
+            }
+        }
+
+        self.remove(agent_id)
+    }
+
+    fn load(&self) -> SessionsFile {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, file: &SessionsFile) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+        fs::write(&self.path, content).map_err(|e| e.to_string())
+    }
+}
+
+/// Whether `session`'s `pid` still refers to the same process that was
+/// recorded, rather than an unrelated process the OS has since recycled the
+/// PID onto.
+fn is_same_process(system: &System, session: &SessionRecord) -> bool {
+    let (Some(pid), Some(started_at)) = (session.pid, session.started_at) else {
+        return false;
+    };
+    system
+        .process(Pid::from_u32(pid))
+        .is_some_and(|process| process.start_time() == started_at)
+}
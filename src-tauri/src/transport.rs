@@ -0,0 +1,359 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+use url::Url;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+#[cfg(not(target_os = "windows"))]
+use shell_escape;
+
+type OnLine = Box<dyn Fn(String) + Send + Sync>;
+type OnStderr = Box<dyn Fn(String) + Send + Sync>;
+type OnClosed = Box<dyn Fn() + Send + Sync>;
+
+/// A duplex, line-oriented channel to a running ACP agent. Hides whether the
+/// agent is a local child process or a socket-connected remote endpoint, so
+/// `AgentManager` can treat both the same way once connected.
+pub trait Transport: Send + Sync {
+    /// Writes one JSON-RPC line (without the trailing newline) to the agent.
+    fn send(&self, line: &str) -> Result<(), String>;
+
+    /// Tears the transport down: kills the child process for a local agent,
+    /// closes the connection gracefully for a remote one.
+    fn close(&self) -> Result<(), String>;
+
+    /// Spawns the background thread(s) that read inbound lines from the
+    /// agent and pass each one to `on_line`, which feeds the JSON-RPC
+    /// dispatcher. `on_stderr` only fires for transports with a separate
+    /// diagnostics stream (stdio); `on_closed` fires once the agent goes
+    /// away.
+    fn spawn_reader(&self, on_line: OnLine, on_stderr: OnStderr, on_closed: OnClosed);
+
+    /// The OS process id backing this transport, for session persistence.
+    /// `None` for remote transports, which have no local process.
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// The original transport: a spawned child process, talked to over its
+/// stdin/stdout/stderr pipes.
+pub struct StdioTransport {
+    child: Mutex<Child>,
+    stdin: Arc<RwLock<std::process::ChildStdin>>,
+    stdout: Mutex<Option<std::process::ChildStdout>>,
+    stderr: Mutex<Option<std::process::ChildStderr>>,
+}
+
+impl StdioTransport {
+    pub fn spawn(
+        command: &str,
+        args: &[String],
+        env: &indexmap::IndexMap<String, String>,
+        cwd: Option<&Path>,
+    ) -> Result<Self, String> {
+        // On Windows, we need to use cmd.exe to properly resolve .cmd/.bat files like npx
+        #[cfg(target_os = "windows")]
+        let mut child = {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C")
+                .arg(command)
+                .args(args)
+                .envs(env)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .creation_flags(0x08000000); // CREATE_NO_WINDOW
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
+            }
+            cmd.spawn()
+                .map_err(|e| format!("Failed to spawn agent: {}", e))?
+        };
+
+        // On macOS/Unix, we need to use /bin/sh -c to properly resolve commands in PATH
+        #[cfg(not(target_os = "windows"))]
+        let mut child = {
+            use std::borrow::Cow;
+
+            // Build shell command with proper quoting for command and arguments
+            let escaped_command = shell_escape::escape(Cow::Borrowed(command));
+            let shell_command = if args.is_empty() {
+                escaped_command.to_string()
+            } else {
+                let quoted_args: Vec<String> = args
+                    .iter()
+                    .map(|arg| shell_escape::escape(Cow::Borrowed(arg.as_str())).to_string())
+                    .collect();
+                format!("{} {}", escaped_command, quoted_args.join(" "))
+            };
+
+            let mut cmd = Command::new("/bin/sh");
+            cmd.arg("-c")
+                .arg(&shell_command)
+                .envs(env)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
+            }
+            cmd.spawn()
+                .map_err(|e| format!("Failed to spawn agent: {}", e))?
+        };
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| "Failed to get stdin".to_string())?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "Failed to get stdout".to_string())?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| "Failed to get stderr".to_string())?;
+
+        Ok(Self {
+            child: Mutex::new(child),
+            stdin: Arc::new(RwLock::new(stdin)),
+            stdout: Mutex::new(Some(stdout)),
+            stderr: Mutex::new(Some(stderr)),
+        })
+    }
+}
+
+impl Transport for StdioTransport {
+    fn send(&self, line: &str) -> Result<(), String> {
+        let mut stdin = self.stdin.write();
+        writeln!(stdin, "{}", line).map_err(|e| format!("Failed to write to stdin: {}", e))?;
+        stdin
+            .flush()
+            .map_err(|e| format!("Failed to flush stdin: {}", e))
+    }
+
+    fn close(&self) -> Result<(), String> {
+        self.child
+            .lock()
+            .unwrap()
+            .kill()
+            .map_err(|e| format!("Failed to kill agent: {}", e))
+    }
+
+    fn spawn_reader(&self, on_line: OnLine, on_stderr: OnStderr, on_closed: OnClosed) {
+        if let Some(stdout) = self.stdout.lock().unwrap().take() {
+            thread::spawn(move || {
+                let reader = BufReader::new(stdout);
+                for line in reader.lines() {
+                    match line {
+                        Ok(message) => on_line(message),
+                        Err(_) => break,
+                    }
+                }
+                on_closed();
+            });
+        }
+
+        if let Some(stderr) = self.stderr.lock().unwrap().take() {
+            thread::spawn(move || {
+                let reader = BufReader::new(stderr);
+                for line in reader.lines() {
+                    match line {
+                        Ok(line_content) => on_stderr(line_content),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.child.lock().unwrap().id())
+    }
+}
+
+/// A remote transport: an agent reachable over a plain TCP socket or a
+/// WebSocket, e.g. one running in a container or on a long-lived daemon.
+pub struct SocketTransport {
+    conn: SocketConn,
+}
+
+/// How often the WebSocket connection thread breaks out of a blocking
+/// `read()` to check for outbound messages queued by `send()`. Keeping this
+/// short bounds how long a send can wait without requiring a true full-duplex
+/// split of the underlying (possibly TLS-wrapped) stream.
+const WS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+enum SocketConn {
+    Tcp(TcpStream),
+    WebSocket {
+        /// `send`/`close` hand their message to the connection thread
+        /// instead of writing to the socket directly, so they never have to
+        /// wait on a lock the reader is holding for a blocking `read()`.
+        outbound: mpsc::Sender<Message>,
+        /// Taken by `spawn_reader`, which is the sole owner of the socket
+        /// (and the receiving half of `outbound`) for the life of the
+        /// connection.
+        socket: Mutex<Option<(WebSocket<MaybeTlsStream<TcpStream>>, mpsc::Receiver<Message>)>>,
+    },
+}
+
+impl SocketTransport {
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid agent URL '{}': {}", url, e))?;
+
+        match parsed.scheme() {
+            "tcp" => {
+                let host = parsed
+                    .host_str()
+                    .ok_or_else(|| format!("Missing host in agent URL '{}'", url))?;
+                let port = parsed
+                    .port()
+                    .ok_or_else(|| format!("Missing port in agent URL '{}'", url))?;
+                let stream = TcpStream::connect((host, port))
+                    .map_err(|e| format!("Failed to connect to '{}': {}", url, e))?;
+                Ok(Self {
+                    conn: SocketConn::Tcp(stream),
+                })
+            }
+            "ws" | "wss" => {
+                let (mut socket, _) =
+                    connect(url).map_err(|e| format!("Failed to connect to '{}': {}", url, e))?;
+                set_read_timeout(socket.get_mut(), Some(WS_POLL_INTERVAL));
+                let (outbound, receiver) = mpsc::channel();
+                Ok(Self {
+                    conn: SocketConn::WebSocket {
+                        outbound,
+                        socket: Mutex::new(Some((socket, receiver))),
+                    },
+                })
+            }
+            other => Err(format!(
+                "Unsupported agent transport scheme '{}' in '{}'",
+                other, url
+            )),
+        }
+    }
+}
+
+/// Best-effort read timeout so the connection thread can periodically check
+/// for outbound messages between blocking reads. Plain and TLS-wrapped
+/// sockets expose this differently; anything else is left blocking, which
+/// only degrades send latency rather than correctness.
+#[allow(unreachable_patterns)]
+fn set_read_timeout(stream: &mut MaybeTlsStream<TcpStream>, timeout: Option<Duration>) {
+    match stream {
+        MaybeTlsStream::Plain(tcp) => {
+            let _ = tcp.set_read_timeout(timeout);
+        }
+        #[cfg(feature = "native-tls")]
+        MaybeTlsStream::NativeTls(tls) => {
+            let _ = tls.get_ref().set_read_timeout(timeout);
+        }
+        _ => {}
+    }
+}
+
+/// Whether a `read()` failure is just the poll interval elapsing with
+/// nothing to read, as opposed to a real connection error.
+fn is_read_timeout(err: &tungstenite::Error) -> bool {
+    matches!(
+        err,
+        tungstenite::Error::Io(e)
+            if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut
+    )
+}
+
+impl Transport for SocketTransport {
+    fn send(&self, line: &str) -> Result<(), String> {
+        match &self.conn {
+            SocketConn::Tcp(stream) => {
+                let mut stream = stream
+                    .try_clone()
+                    .map_err(|e| format!("Failed to write to agent socket: {}", e))?;
+                writeln!(stream, "{}", line)
+                    .map_err(|e| format!("Failed to write to agent socket: {}", e))
+            }
+            SocketConn::WebSocket { outbound, .. } => outbound
+                .send(Message::Text(line.to_string()))
+                .map_err(|e| format!("Failed to send to agent: {}", e)),
+        }
+    }
+
+    fn close(&self) -> Result<(), String> {
+        match &self.conn {
+            SocketConn::Tcp(stream) => stream
+                .shutdown(std::net::Shutdown::Both)
+                .map_err(|e| format!("Failed to close agent socket: {}", e)),
+            SocketConn::WebSocket { outbound, .. } => outbound
+                .send(Message::Close(None))
+                .map_err(|e| format!("Failed to close agent socket: {}", e)),
+        }
+    }
+
+    fn spawn_reader(&self, on_line: OnLine, _on_stderr: OnStderr, on_closed: OnClosed) {
+        match &self.conn {
+            SocketConn::Tcp(stream) => {
+                let stream = match stream.try_clone() {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                thread::spawn(move || {
+                    let reader = BufReader::new(stream);
+                    for line in reader.lines() {
+                        match line {
+                            Ok(message) => on_line(message),
+                            Err(_) => break,
+                        }
+                    }
+                    on_closed();
+                });
+            }
+            SocketConn::WebSocket { socket, .. } => {
+                // This thread is the sole owner of the socket: it drains
+                // `outbound` (fed by `send`/`close` from any thread) between
+                // reads, so a writer is never blocked on a lock held for the
+                // duration of a blocking `read()`.
+                let Some((mut socket, receiver)) = socket.lock().unwrap().take() else {
+                    return;
+                };
+                thread::spawn(move || {
+                    loop {
+                        let mut should_close = false;
+                        while let Ok(message) = receiver.try_recv() {
+                            should_close |= matches!(message, Message::Close(_));
+                            if socket.send(message).is_err() {
+                                should_close = true;
+                                break;
+                            }
+                        }
+                        if should_close {
+                            let _ = socket.close(None);
+                            break;
+                        }
+
+                        match socket.read() {
+                            Ok(Message::Text(text)) => on_line(text),
+                            Ok(Message::Close(_)) => break,
+                            Ok(_) => {}
+                            Err(ref err) if is_read_timeout(err) => continue,
+                            Err(_) => break,
+                        }
+                    }
+                    on_closed();
+                });
+            }
+        }
+    }
+}